@@ -1,6 +1,11 @@
-use super::toml::{ConfigToml, ReverseProxyOption};
+use super::toml::{
+  CompressionOption, ConfigToml, HealthCheckOption, RedirectOption, ReverseProxyOption, UpstreamTlsOption,
+};
 use crate::{
-  backend::{Backend, PathNameLC, ReverseProxy, UpstreamGroup},
+  backend::{
+    Backend, CompressionSetting, HealthCheckSetting, LbStrategy, PathNameLC, RedirectAction, RedirectTarget,
+    ReverseProxy, StickyAffinity, TlsVersion, UpstreamGroup, UpstreamTlsSetting,
+  },
   backend_opt::UpstreamOption,
   constants::*,
   error::*,
@@ -32,6 +37,30 @@ pub fn parse_opts(globals: &mut Globals) -> std::result::Result<(), anyhow::Erro
     ConfigToml::default()
   };
 
+  apply_config(config, globals)
+}
+
+// Top-level config schema version. Bumped whenever a config change would otherwise
+// be parsed silently-wrong by an older parser; unknown or missing versions are a hard error
+// rather than a best-effort guess.
+const SUPPORTED_CONFIG_VERSION: &str = "v1";
+
+pub(crate) fn apply_config(config: ConfigToml, globals: &mut Globals) -> std::result::Result<(), anyhow::Error> {
+  match config.version.as_deref() {
+    Some(SUPPORTED_CONFIG_VERSION) => apply_config_v1(config, globals),
+    Some(other) => bail!(
+      "Unsupported config version \"{}\" (this build supports \"{}\")",
+      other,
+      SUPPORTED_CONFIG_VERSION
+    ),
+    None => bail!(
+      "Missing required `version` field in config (expected version = \"{}\")",
+      SUPPORTED_CONFIG_VERSION
+    ),
+  }
+}
+
+fn apply_config_v1(config: ConfigToml, globals: &mut Globals) -> std::result::Result<(), anyhow::Error> {
   // listen port and socket
   globals.http_port = config.listen_port;
   globals.https_port = config.listen_port_tls;
@@ -89,45 +118,40 @@ pub fn parse_opts(globals: &mut Globals) -> std::result::Result<(), anyhow::Erro
   let apps = config.apps.unwrap();
   ensure!(!apps.0.is_empty(), "Wrong application spec.");
 
+  // All cross-cutting checks (duplicates, TLS/port consistency, cert coverage, path
+  // overlaps, default_app) are centralized here so operators see every problem at once
+  // instead of fixing one `ensure!` failure at a time.
+  validate_apps(&apps, globals.http_port, globals.https_port, &config.default_app)?;
+
   // each app
   for (app_name, app) in apps.0.iter() {
-    ensure!(app.server_name.is_some(), "Missing server_name");
     let server_name = app.server_name.as_ref().unwrap().to_ascii_lowercase();
 
     // TLS settings
     let (tls_cert_path, tls_cert_key_path, https_redirection) = if app.tls.is_none() {
-      ensure!(globals.http_port.is_some(), "Required HTTP port");
       (None, None, None)
     } else {
       let tls = app.tls.as_ref().unwrap();
-      ensure!(tls.tls_cert_key_path.is_some() && tls.tls_cert_path.is_some());
-
       (
         tls.tls_cert_path.as_ref().map(PathBuf::from),
         tls.tls_cert_key_path.as_ref().map(PathBuf::from),
-        if tls.https_redirection.is_none() {
-          Some(true) // Default true
-        } else {
-          ensure!(globals.https_port.is_some()); // only when both https ports are configured.
-          tls.https_redirection
-        },
+        Some(tls.https_redirection.unwrap_or(true)), // Default true
       )
     };
-    if globals.http_port.is_none() {
-      // if only https_port is specified, tls must be configured
-      ensure!(app.tls.is_some())
-    }
 
     // reverse proxy settings
-    ensure!(app.reverse_proxy.is_some(), "Missing reverse_proxy");
     let reverse_proxy = get_reverse_proxy(app.reverse_proxy.as_ref().unwrap())?;
 
+    // response compression settings
+    let compression = app.compression.as_ref().map(get_compression).transpose()?;
+
     globals.backends.apps.insert(
       server_name.as_bytes().to_vec(),
       Backend {
         app_name: app_name.to_owned(),
         server_name: server_name.to_owned(),
         reverse_proxy,
+        compression,
 
         tls_cert_path,
         tls_cert_key_path,
@@ -191,9 +215,193 @@ pub fn parse_opts(globals: &mut Globals) -> std::result::Result<(), anyhow::Erro
   Ok(())
 }
 
+// Centralized validation pass over the whole `apps` table. Collects *all* problems found by
+// the checks below before returning, so a single `-c config.toml` run reports them together
+// rather than making them fix-and-rerun one `ensure!` at a time. Per-entry option parsing
+// (`get_reverse_proxy`, `get_compression`) runs after this pass and stops at its first error.
+fn validate_apps(
+  apps: &crate::config::toml::Apps,
+  http_port: Option<u16>,
+  https_port: Option<u16>,
+  default_app: &Option<String>,
+) -> std::result::Result<(), anyhow::Error> {
+  let mut errors: Vec<String> = Vec::new();
+  let mut seen_server_names: HashSet<String> = HashSet::default();
+
+  for (app_name, app) in apps.0.iter() {
+    match &app.server_name {
+      None => errors.push(format!("app \"{}\": missing server_name", app_name)),
+      Some(sn) => {
+        let sn_lc = sn.to_ascii_lowercase();
+        if !seen_server_names.insert(sn_lc.clone()) {
+          errors.push(format!("duplicate server_name \"{}\" (app \"{}\")", sn_lc, app_name));
+        }
+      }
+    }
+
+    match &app.tls {
+      None => {
+        if http_port.is_none() {
+          errors.push(format!(
+            "app \"{}\": only an HTTPS port is bound, but no tls is configured",
+            app_name
+          ));
+        }
+      }
+      Some(tls) => match (&tls.tls_cert_path, &tls.tls_cert_key_path) {
+        (Some(cert), Some(key)) => {
+          let cert_path = PathBuf::from(cert);
+          let key_path = PathBuf::from(key);
+          if !cert_path.is_file() {
+            errors.push(format!("app \"{}\": tls_cert_path does not exist: {:?}", app_name, cert_path));
+          } else if let Some(sn) = &app.server_name {
+            if let Err(e) = cert_covers_server_name(&cert_path, sn) {
+              errors.push(format!("app \"{}\": {}", app_name, e));
+            }
+          }
+          if !key_path.is_file() {
+            errors.push(format!("app \"{}\": tls_cert_key_path does not exist: {:?}", app_name, key_path));
+          } else if let Err(e) = validate_private_key(&key_path) {
+            errors.push(format!("app \"{}\": tls_cert_key_path {:?} is unreadable: {}", app_name, key_path, e));
+          }
+        }
+        _ => errors.push(format!(
+          "app \"{}\": tls_cert_path and tls_cert_key_path must both be set",
+          app_name
+        )),
+      },
+    }
+    // `https_redirection` defaults to `true` whenever `tls` is configured (see apply_config_v1),
+    // so the HTTPS-port requirement must fire on that resolved default, not just on an explicit `Some`.
+    if let Some(tls) = &app.tls {
+      if tls.https_redirection.unwrap_or(true) && https_port.is_none() {
+        errors.push(format!(
+          "app \"{}\": https_redirection is enabled (the default, unless set to false) but no HTTPS port is configured",
+          app_name
+        ));
+      }
+    }
+
+    match &app.reverse_proxy {
+      None => errors.push(format!("app \"{}\": missing reverse_proxy", app_name)),
+      Some(rp) => {
+        for (a, b) in find_overlapping_paths(rp) {
+          errors.push(format!(
+            "app \"{}\": overlapping reverse_proxy path prefixes \"{}\" and \"{}\"",
+            app_name, a, b
+          ));
+        }
+      }
+    }
+  }
+
+  if let Some(d) = default_app {
+    if !apps.0.contains_key(d) {
+      errors.push(format!("default_app \"{}\" does not match any configured app", d));
+    }
+  }
+
+  ensure!(
+    errors.is_empty(),
+    "Invalid configuration:\n{}",
+    errors.iter().map(|e| format!("  - {}", e)).collect::<Vec<_>>().join("\n")
+  );
+  Ok(())
+}
+
+// Two reverse_proxy entries "overlap" when one's path is a strict prefix of the other's
+// *on a segment boundary* (so `/api` overlaps `/api/v2` but not `/api-v2`), since a request
+// could then match either depending on map iteration order. The `/` default is exempt: it's
+// meant to catch everything not matched by a more specific entry, so it always "overlaps"
+// every other path without that being a misconfiguration.
+fn find_overlapping_paths(rp_settings: &[ReverseProxyOption]) -> Vec<(String, String)> {
+  let paths: Vec<String> = rp_settings
+    .iter()
+    .map(|rpo| rpo.path.clone().unwrap_or_else(|| "/".to_string()))
+    .filter(|p| p != "/")
+    .collect();
+  let mut overlaps = Vec::new();
+  for (i, a) in paths.iter().enumerate() {
+    for b in paths.iter().skip(i + 1) {
+      if a != b && is_segment_prefix(a, b) {
+        overlaps.push((a.clone(), b.clone()));
+      }
+    }
+  }
+  overlaps
+}
+
+// True if the shorter of `a`/`b` is a prefix of the longer one ending exactly on a `/`
+// segment boundary, e.g. `/api` vs `/api/v2`, but not `/api` vs `/api-v2`.
+fn is_segment_prefix(a: &str, b: &str) -> bool {
+  let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+  if !longer.starts_with(shorter) {
+    return false;
+  }
+  shorter.ends_with('/') || longer.as_bytes()[shorter.len()] == b'/'
+}
+
+// Checks that the leaf certificate's SAN (or, failing that, its CN) covers `server_name`,
+// matching a single leading wildcard label the same way TLS clients do.
+fn cert_covers_server_name(cert_path: &PathBuf, server_name: &str) -> std::result::Result<(), anyhow::Error> {
+  let pem = std::fs::read(cert_path)?;
+  let mut reader = std::io::BufReader::new(pem.as_slice());
+  let certs = rustls_pemfile::certs(&mut reader)?;
+  let der = certs
+    .first()
+    .ok_or_else(|| anyhow!("no certificate found in {:?}", cert_path))?;
+  let (_, parsed) =
+    x509_parser::parse_x509_certificate(der).map_err(|e| anyhow!("failed to parse certificate {:?}: {}", cert_path, e))?;
+
+  let domain = server_name.to_ascii_lowercase();
+  let san_covers = parsed
+    .subject_alternative_name()
+    .ok()
+    .flatten()
+    .map(|san| {
+      san.value.general_names.iter().any(|gn| match gn {
+        x509_parser::extensions::GeneralName::DNSName(d) => domain_matches(d, &domain),
+        _ => false,
+      })
+    })
+    .unwrap_or(false);
+  let cn_covers = parsed
+    .subject()
+    .iter_common_name()
+    .any(|cn| cn.as_str().map(|s| domain_matches(s, &domain)).unwrap_or(false));
+
+  ensure!(
+    san_covers || cn_covers,
+    "certificate {:?} does not cover server_name \"{}\" (checked SAN/CN)",
+    cert_path,
+    server_name
+  );
+  Ok(())
+}
+
+// Confirms the key file actually contains a parseable private key, rather than just
+// existing — a truncated or wrong-format file otherwise only fails at TLS handshake time.
+fn validate_private_key(key_path: &PathBuf) -> std::result::Result<(), anyhow::Error> {
+  let bytes = std::fs::read(key_path)?;
+  let mut reader = std::io::BufReader::new(bytes.as_slice());
+  let key = rustls_pemfile::private_key(&mut reader)?;
+  ensure!(key.is_some(), "no private key found in {:?}", key_path);
+  Ok(())
+}
+
+fn domain_matches(pattern: &str, domain: &str) -> bool {
+  let pattern = pattern.to_ascii_lowercase();
+  match pattern.strip_prefix("*.") {
+    Some(rest) => {
+      domain.ends_with(&format!(".{}", rest)) && domain.matches('.').count() == rest.matches('.').count() + 1
+    }
+    None => pattern == domain,
+  }
+}
+
 fn get_reverse_proxy(rp_settings: &[ReverseProxyOption]) -> std::result::Result<ReverseProxy, anyhow::Error> {
   let mut upstream: HashMap<PathNameLC, UpstreamGroup> = HashMap::default();
-  rp_settings.iter().for_each(|rpo| {
+  rp_settings.iter().try_for_each(|rpo| -> std::result::Result<(), anyhow::Error> {
     let path = match &rpo.path {
       Some(p) => p.as_bytes().to_ascii_lowercase(),
       None => "/".as_bytes().to_ascii_lowercase(),
@@ -206,8 +414,11 @@ fn get_reverse_proxy(rp_settings: &[ReverseProxyOption]) -> std::result::Result<
         .replace_path
         .as_ref()
         .map_or_else(|| None, |v| Some(v.as_bytes().to_ascii_lowercase())),
+      redirect: rpo.redirect_to.as_ref().map(to_redirect_action).transpose()?,
+      health_check: rpo.health_check.as_ref().map(to_health_check).transpose()?,
+      tls: rpo.tls.as_ref().map(to_upstream_tls).transpose()?,
       cnt: Default::default(),
-      lb: Default::default(),
+      lb: to_lb_strategy(rpo)?,
       opts: {
         if let Some(opts) = &rpo.upstream_options {
           opts
@@ -221,10 +432,181 @@ fn get_reverse_proxy(rp_settings: &[ReverseProxyOption]) -> std::result::Result<
     };
 
     upstream.insert(path, elem);
-  });
+    Ok(())
+  })?;
   ensure!(
     rp_settings.iter().filter(|rpo| rpo.path.is_none()).count() < 2,
     "Multiple default reverse proxy setting"
   );
   Ok(ReverseProxy { upstream })
 }
+
+// Known `Content-Encoding` names accepted in `compression.encodings`.
+const KNOWN_ENCODINGS: &[&str] = &["gzip", "br", "deflate"];
+
+// NOTE: this only parses and validates `compression` into a `CompressionSetting`. Actually
+// negotiating `Accept-Encoding` and stream-compressing matching response bodies is runtime
+// behavior that belongs to the response path, not config parsing, and is not implemented here.
+fn get_compression(opt: &CompressionOption) -> std::result::Result<CompressionSetting, anyhow::Error> {
+  let encodings: HashSet<String> = match &opt.encodings {
+    Some(v) => {
+      ensure!(!v.is_empty(), "compression.encodings must not be empty when specified");
+      v.iter()
+        .map(|e| {
+          let lc = e.to_ascii_lowercase();
+          ensure!(
+            KNOWN_ENCODINGS.contains(&lc.as_str()),
+            "Unknown compression encoding: {} (must be one of {:?})",
+            e,
+            KNOWN_ENCODINGS
+          );
+          Ok(lc)
+        })
+        .collect::<std::result::Result<_, anyhow::Error>>()?
+    }
+    None => KNOWN_ENCODINGS.iter().map(|s| s.to_string()).collect(),
+  };
+
+  let mime_types: HashSet<String> = opt
+    .mime_types
+    .as_ref()
+    .map(|v| v.iter().map(|m| m.to_ascii_lowercase()).collect())
+    .unwrap_or_else(|| {
+      ["text/html", "text/css", "text/javascript", "application/javascript", "application/json"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+    });
+
+  Ok(CompressionSetting { encodings, mime_types })
+}
+
+fn parse_tls_version(v: &str) -> std::result::Result<TlsVersion, anyhow::Error> {
+  match v {
+    "1.2" | "TLSv1.2" => Ok(TlsVersion::Tls12),
+    "1.3" | "TLSv1.3" => Ok(TlsVersion::Tls13),
+    other => Err(anyhow!("Unsupported tls version: {} (must be \"1.2\" or \"1.3\")", other)),
+  }
+}
+
+// NOTE: this parses and validates the upstream TLS options into an `UpstreamTlsSetting` only.
+// Building the dedicated rustls `ClientConfig` (loading the client cert/key chain, pinning
+// min/max protocol version, honoring `insecure_skip_verify`) is done by the connection pool
+// when it dials an upstream, not here.
+fn to_upstream_tls(opt: &UpstreamTlsOption) -> std::result::Result<UpstreamTlsSetting, anyhow::Error> {
+  let client_cert_path = opt.client_cert_path.as_ref().map(PathBuf::from);
+  let client_key_path = opt.client_key_path.as_ref().map(PathBuf::from);
+  ensure!(
+    client_cert_path.is_some() == client_key_path.is_some(),
+    "upstream tls.client_cert_path and tls.client_key_path must be set together"
+  );
+  if let Some(p) = &client_cert_path {
+    ensure!(p.is_file(), "upstream tls.client_cert_path does not exist: {:?}", p);
+  }
+  if let Some(p) = &client_key_path {
+    ensure!(p.is_file(), "upstream tls.client_key_path does not exist: {:?}", p);
+  }
+
+  let root_ca_path = opt.root_ca_path.as_ref().map(PathBuf::from);
+  if let Some(p) = &root_ca_path {
+    ensure!(p.is_file(), "upstream tls.root_ca_path does not exist: {:?}", p);
+  }
+
+  let min_version = opt.min_tls_version.as_deref().map(parse_tls_version).transpose()?;
+  let max_version = opt.max_tls_version.as_deref().map(parse_tls_version).transpose()?;
+  if let (Some(min), Some(max)) = (min_version, max_version) {
+    ensure!(min <= max, "upstream tls.min_tls_version must not exceed tls.max_tls_version");
+  }
+
+  Ok(UpstreamTlsSetting {
+    client_cert_path,
+    client_key_path,
+    root_ca_path,
+    min_version,
+    max_version,
+    sni_override: opt.sni_override.clone(),
+    insecure_skip_verify: opt.insecure_skip_verify.unwrap_or(false),
+  })
+}
+
+// `weight` on an individual upstream is only meaningful under `weighted_round_robin`;
+// any other strategy treats a configured weight as a mistake rather than silently ignoring it.
+//
+// NOTE: this parses and validates the selected strategy only. `LbStrategy::WeightedRoundRobin`
+// carries the configured weights but no per-upstream "current" counter, and actual upstream
+// selection (smooth WRR, least-connection, sticky affinity/cookie) is implemented by the proxy
+// core at dispatch time, not here.
+fn to_lb_strategy(rpo: &ReverseProxyOption) -> std::result::Result<LbStrategy, anyhow::Error> {
+  let weights_given = rpo.upstream.iter().any(|u| u.weight.is_some());
+  let strategy = match rpo.lb.as_deref() {
+    None | Some("round_robin") => {
+      ensure!(!weights_given, "`weight` is only valid with lb = \"weighted_round_robin\"");
+      LbStrategy::RoundRobin
+    }
+    Some("weighted_round_robin") => {
+      let weights: Vec<u32> = rpo.upstream.iter().map(|u| u.weight.unwrap_or(1)).collect();
+      ensure!(weights.iter().all(|w| *w > 0), "`weight` must be a positive integer");
+      LbStrategy::WeightedRoundRobin(weights)
+    }
+    Some("least_connection") => {
+      ensure!(!weights_given, "`weight` is only valid with lb = \"weighted_round_robin\"");
+      LbStrategy::LeastConnection
+    }
+    Some("sticky") => {
+      ensure!(!weights_given, "`weight` is only valid with lb = \"weighted_round_robin\"");
+      let affinity = match rpo.sticky.as_ref().and_then(|s| s.cookie_name.clone()) {
+        Some(cookie_name) => StickyAffinity::Cookie(cookie_name),
+        None => StickyAffinity::ClientIp,
+      };
+      LbStrategy::Sticky(affinity)
+    }
+    Some(other) => bail!("Unknown load balancing strategy: {}", other),
+  };
+  Ok(strategy)
+}
+
+// Default active health-check cadence when `health_check` omits `interval_sec`/`timeout_sec`.
+const DEFAULT_HEALTH_CHECK_INTERVAL_SEC: u64 = 10;
+const DEFAULT_HEALTH_CHECK_TIMEOUT_SEC: u64 = 3;
+
+fn to_health_check(opt: &HealthCheckOption) -> std::result::Result<HealthCheckSetting, anyhow::Error> {
+  let interval_sec = opt.interval_sec.unwrap_or(DEFAULT_HEALTH_CHECK_INTERVAL_SEC);
+  let timeout_sec = opt.timeout_sec.unwrap_or(DEFAULT_HEALTH_CHECK_TIMEOUT_SEC);
+  ensure!(timeout_sec < interval_sec, "health_check.timeout_sec must be shorter than interval_sec");
+
+  let (expected_status_min, expected_status_max) = opt.expected_status_range.unwrap_or((200, 399));
+  ensure!(
+    expected_status_min <= expected_status_max,
+    "health_check.expected_status_range must be a non-empty range"
+  );
+
+  let healthy_threshold = opt.healthy_threshold.unwrap_or(2);
+  let unhealthy_threshold = opt.unhealthy_threshold.unwrap_or(3);
+  ensure!(healthy_threshold > 0, "health_check.healthy_threshold must be positive");
+  ensure!(unhealthy_threshold > 0, "health_check.unhealthy_threshold must be positive");
+
+  Ok(HealthCheckSetting {
+    interval_sec,
+    timeout_sec,
+    path: opt.path.clone().unwrap_or_else(|| "/".to_string()),
+    expected_status_min,
+    expected_status_max,
+    healthy_threshold,
+    unhealthy_threshold,
+  })
+}
+
+// Status codes accepted for `redirect_to`: permanent (308, the default, or legacy 301)
+// and temporary (302, or 307 to preserve the request method).
+fn to_redirect_action(redirect_to: &RedirectOption) -> std::result::Result<RedirectAction, anyhow::Error> {
+  let status_code = redirect_to.status_code.unwrap_or(308);
+  let target = RedirectTarget {
+    location: redirect_to.to.clone(),
+    append_path: redirect_to.append_path.unwrap_or(true),
+  };
+  match status_code {
+    301 | 308 => Ok(RedirectAction::Permanent(target)),
+    302 | 307 => Ok(RedirectAction::Temporary(target)),
+    _ => bail!("Invalid redirect_to.status_code: must be one of 301, 302, 307, 308"),
+  }
+}
@@ -0,0 +1,57 @@
+use super::{parse::apply_config, toml::ConfigToml};
+use crate::{backend::Backends, error::*, globals::Globals, log::*};
+use std::{path::PathBuf, sync::Arc};
+use tokio::sync::watch;
+
+// How often the config file's mtime is polled for changes. rpxy is typically run with a
+// handful of backends, so a lightweight poll loop is preferred over pulling in a platform
+// file-notification backend for this.
+const WATCH_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Watches `config_file_path` for changes and republishes a freshly-built [`Backends`] map on
+/// `tx` whenever the file changes and re-parses cleanly. Only the backend map is swapped, as
+/// specified, not the whole [`Globals`] (ports, max_clients, etc. are fixed at process start).
+/// A failed reload is logged and the previously-published backends are kept in place, so a
+/// typo in the config never drops already-accepted connections. The rebuild always starts
+/// from an empty [`Backends`], so an app or upstream removed from the file is actually dropped
+/// rather than lingering from the previous generation.
+pub async fn watch_config(config_file_path: PathBuf, base: Globals, tx: watch::Sender<Arc<Backends>>) {
+  let mut last_modified = std::fs::metadata(&config_file_path).and_then(|m| m.modified()).ok();
+
+  loop {
+    tokio::time::sleep(WATCH_POLL_INTERVAL).await;
+
+    let modified = match std::fs::metadata(&config_file_path).and_then(|m| m.modified()) {
+      Ok(m) => m,
+      Err(e) => {
+        warn!("Failed to stat config file {:?} for hot reload: {}", config_file_path, e);
+        continue;
+      }
+    };
+    if Some(modified) == last_modified {
+      continue;
+    }
+    last_modified = Some(modified);
+
+    match reload_once(&config_file_path, &base) {
+      Ok(backends) => {
+        info!("Config file changed, reloaded backends from {:?}", config_file_path);
+        let _ = tx.send(Arc::new(backends));
+      }
+      Err(e) => {
+        error!("Ignoring config reload from {:?}: {}", config_file_path, e);
+      }
+    }
+  }
+}
+
+fn reload_once(config_file_path: &PathBuf, base: &Globals) -> std::result::Result<Backends, anyhow::Error> {
+  let config = ConfigToml::new(config_file_path.to_str().ok_or_else(|| anyhow!("Invalid config path"))?)?;
+  // Start from a clean slate: `apply_config` only ever inserts into `backends.apps`, so
+  // reusing `base.backends` as-is would leave apps/upstreams removed from the file still
+  // being served from the previous generation.
+  let mut scratch = base.clone();
+  scratch.backends = Backends::default();
+  apply_config(config, &mut scratch)?;
+  Ok(scratch.backends)
+}